@@ -0,0 +1,501 @@
+use std::{
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{DNSClass, Name, Record, RecordType},
+};
+use lru::LruCache;
+
+use crate::{nsec::AggressiveNegativeCache, ttl::aged_ttl};
+
+/// Bounds and sizing for [`DnsCache`], configured per-listener via
+/// [`crate::DNSListenAddr::cache`].
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub size: usize,
+    pub min_ttl: u32,
+    pub max_ttl: u32,
+    /// Enable RFC 8198 aggressive use of cached NSEC/NSEC3 denial-of-existence
+    /// ranges to synthesize NXDOMAIN answers for DO-bit queries without
+    /// consulting upstream. NODATA answers are not synthesized.
+    pub aggressive_nsec: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            size: 4096,
+            min_ttl: 1,
+            max_ttl: 86400,
+            aggressive_nsec: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+struct CacheKey {
+    name: Name,
+    record_type: RecordType,
+    dns_class: DNSClass,
+}
+
+#[derive(Debug, Clone)]
+enum Answer {
+    Positive {
+        records: Vec<Record>,
+        rrsigs: Vec<Record>,
+    },
+    Negative {
+        response_code: ResponseCode,
+        soa: Option<Record>,
+        rrsigs: Vec<Record>,
+    },
+}
+
+/// A negative answer synthesized locally from a cached NSEC/NSEC3 gap,
+/// carrying the covering records to serve in the authority section.
+pub struct NegativeSynthesis {
+    pub response_code: ResponseCode,
+    pub authority: Vec<Record>,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    answer: Answer,
+    inserted_at: Instant,
+    ttl: u32,
+}
+
+impl CacheEntry {
+    /// The TTL once aged by the time elapsed since insertion. Returns `None`
+    /// once the entry's TTL has been exhausted.
+    fn aged_ttl(&self) -> Option<u32> {
+        aged_ttl(self.inserted_at, self.ttl)
+    }
+}
+
+/// A resolved answer served out of the cache, with TTLs already aged down
+/// to reflect the time spent sitting in the cache.
+pub struct CacheHit {
+    pub response_code: ResponseCode,
+    pub records: Vec<Record>,
+    pub rrsigs: Vec<Record>,
+    pub soa: Option<Record>,
+}
+
+/// LRU cache of resolved DNS answers, keyed by `(Name, RecordType, DNSClass)`.
+///
+/// Positive answers age their TTLs down on every lookup and are evicted once
+/// exhausted; NXDOMAIN/NODATA responses are cached negatively using the
+/// zone's SOA `minimum` as their TTL (RFC 2308). Entries that were resolved
+/// under a signed zone keep their RRSIG records so that a later query with
+/// the DO bit set can still be answered from the cache; if the requester
+/// wants DNSSEC data but the entry holds no signatures, the lookup misses so
+/// the query can be forwarded upstream instead.
+pub struct DnsCache {
+    inner: Mutex<LruCache<CacheKey, CacheEntry>>,
+    min_ttl: u32,
+    max_ttl: u32,
+    aggressive: Option<AggressiveNegativeCache>,
+}
+
+impl DnsCache {
+    pub fn new(config: &CacheConfig) -> Self {
+        let size = NonZeroUsize::new(config.size).unwrap_or(NonZeroUsize::new(1).unwrap());
+        Self {
+            inner: Mutex::new(LruCache::new(size)),
+            min_ttl: config.min_ttl,
+            max_ttl: config.max_ttl,
+            aggressive: config
+                .aggressive_nsec
+                .then(|| AggressiveNegativeCache::new(config.size)),
+        }
+    }
+
+    /// Synthesizes a denial of existence for `name` from a previously
+    /// observed, still-valid NSEC/NSEC3 gap, if aggressive mode is enabled
+    /// and the requester set the DO bit.
+    pub fn synthesize_negative(&self, name: &Name, dnssec_ok: bool) -> Option<NegativeSynthesis> {
+        if !dnssec_ok {
+            return None;
+        }
+        let synthesized = self.aggressive.as_ref()?.synthesize(name)?;
+        Some(NegativeSynthesis {
+            response_code: ResponseCode::NXDomain,
+            authority: synthesized.authority,
+        })
+    }
+
+    fn clamp_ttl(&self, ttl: u32) -> u32 {
+        ttl.clamp(self.min_ttl, self.max_ttl)
+    }
+
+    pub fn get(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+        dns_class: DNSClass,
+        dnssec_ok: bool,
+    ) -> Option<CacheHit> {
+        let key = CacheKey {
+            name: name.clone(),
+            record_type,
+            dns_class,
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        let ttl = match inner.get(&key).and_then(CacheEntry::aged_ttl) {
+            Some(ttl) => ttl,
+            None => {
+                inner.pop(&key);
+                return None;
+            }
+        };
+        let entry = inner.get(&key).expect("just confirmed present above");
+
+        match &entry.answer {
+            Answer::Positive { records, rrsigs } => {
+                if dnssec_ok && rrsigs.is_empty() {
+                    return None;
+                }
+                let records = records
+                    .iter()
+                    .cloned()
+                    .map(|mut r| {
+                        r.set_ttl(ttl);
+                        r
+                    })
+                    .collect();
+                let rrsigs = if dnssec_ok {
+                    rrsigs
+                        .iter()
+                        .cloned()
+                        .map(|mut r| {
+                            r.set_ttl(ttl);
+                            r
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                Some(CacheHit {
+                    response_code: ResponseCode::NoError,
+                    records,
+                    rrsigs,
+                    soa: None,
+                })
+            }
+            Answer::Negative {
+                response_code,
+                soa,
+                rrsigs,
+            } => {
+                // Mirrors the positive-answer rule above: a negative entry
+                // with no covering RRSIG is an unsigned denial, so a DO=1
+                // requester must not be served it from the cache.
+                if dnssec_ok && rrsigs.is_empty() {
+                    return None;
+                }
+                Some(CacheHit {
+                    response_code: *response_code,
+                    records: Vec::new(),
+                    rrsigs: if dnssec_ok {
+                        rrsigs
+                            .iter()
+                            .cloned()
+                            .map(|mut r| {
+                                r.set_ttl(ttl);
+                                r
+                            })
+                            .collect()
+                    } else {
+                        Vec::new()
+                    },
+                    soa: soa.clone().map(|mut r| {
+                        r.set_ttl(ttl);
+                        r
+                    }),
+                })
+            }
+        }
+    }
+
+    pub fn insert_positive(
+        &self,
+        name: Name,
+        record_type: RecordType,
+        dns_class: DNSClass,
+        records: &[Record],
+    ) {
+        let Some(min_ttl) = records.iter().map(Record::ttl).min() else {
+            return;
+        };
+        let rrsigs = records
+            .iter()
+            .filter(|r| r.record_type() == RecordType::RRSIG)
+            .cloned()
+            .collect();
+        let records = records
+            .iter()
+            .filter(|r| r.record_type() != RecordType::RRSIG)
+            .cloned()
+            .collect();
+
+        self.insert(
+            name,
+            record_type,
+            dns_class,
+            Answer::Positive { records, rrsigs },
+            min_ttl,
+        );
+    }
+
+    /// Caches a negative (NXDOMAIN/NODATA) answer. `authority` is the whole
+    /// authority section of the upstream response: the SOA `minimum` field is
+    /// used as the negative TTL (RFC 2308), and if aggressive mode is enabled any
+    /// NSEC/NSEC3 + RRSIG records found there are kept to synthesize answers
+    /// for other names that fall in the same gap.
+    pub fn insert_negative(
+        &self,
+        name: Name,
+        record_type: RecordType,
+        dns_class: DNSClass,
+        response_code: ResponseCode,
+        authority: &[Record],
+    ) {
+        let soa = authority
+            .iter()
+            .find(|r| r.record_type() == RecordType::SOA)
+            .cloned();
+        let rrsigs: Vec<Record> = authority
+            .iter()
+            .filter(|r| r.record_type() == RecordType::RRSIG)
+            .cloned()
+            .collect();
+        let ttl = soa
+            .as_ref()
+            .and_then(|r| r.data().and_then(|d| d.as_soa()))
+            .map(|soa| soa.minimum())
+            .unwrap_or(self.min_ttl);
+
+        if let Some(aggressive) = &self.aggressive {
+            if let Some(zone) = soa.as_ref().map(Record::name) {
+                aggressive.observe(zone, authority);
+            }
+        }
+
+        self.insert(
+            name,
+            record_type,
+            dns_class,
+            Answer::Negative {
+                response_code,
+                soa,
+                rrsigs,
+            },
+            ttl,
+        );
+    }
+
+    fn insert(
+        &self,
+        name: Name,
+        record_type: RecordType,
+        dns_class: DNSClass,
+        answer: Answer,
+        ttl: u32,
+    ) {
+        let key = CacheKey {
+            name,
+            record_type,
+            dns_class,
+        };
+        let entry = CacheEntry {
+            answer,
+            inserted_at: Instant::now(),
+            ttl: self.clamp_ttl(ttl),
+        };
+        self.inner.lock().unwrap().put(key, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, thread::sleep};
+
+    use hickory_proto::rr::{rdata::A, RData};
+
+    use super::*;
+
+    fn a_record(name: &str, ttl: u32) -> Record {
+        Record::from_rdata(
+            name.parse().unwrap(),
+            ttl,
+            RData::A(A::new(93, 184, 215, 14)),
+        )
+    }
+
+    #[test]
+    fn positive_hit_ages_ttl_and_expires() {
+        let cache = DnsCache::new(&CacheConfig {
+            size: 16,
+            min_ttl: 0,
+            max_ttl: 3600,
+            aggressive_nsec: false,
+        });
+        let name: Name = "www.example.com.".parse().unwrap();
+        cache.insert_positive(
+            name.clone(),
+            RecordType::A,
+            DNSClass::IN,
+            &[a_record("www.example.com.", 1)],
+        );
+
+        let hit = cache
+            .get(&name, RecordType::A, DNSClass::IN, false)
+            .expect("cache hit");
+        assert_eq!(hit.records.len(), 1);
+        assert!(hit.records[0].ttl() <= 1);
+
+        sleep(Duration::from_secs(2));
+        assert!(cache.get(&name, RecordType::A, DNSClass::IN, false).is_none());
+    }
+
+    #[test]
+    fn min_max_ttl_are_clamped() {
+        let cache = DnsCache::new(&CacheConfig {
+            size: 16,
+            min_ttl: 30,
+            max_ttl: 60,
+            aggressive_nsec: false,
+        });
+        let name: Name = "clamped.example.com.".parse().unwrap();
+        cache.insert_positive(
+            name.clone(),
+            RecordType::A,
+            DNSClass::IN,
+            &[a_record("clamped.example.com.", 5)],
+        );
+        let hit = cache
+            .get(&name, RecordType::A, DNSClass::IN, false)
+            .expect("cache hit");
+        assert_eq!(hit.records[0].ttl(), 30);
+    }
+
+    #[test]
+    fn dnssec_request_misses_without_cached_rrsig() {
+        let cache = DnsCache::new(&CacheConfig::default());
+        let name: Name = "plain.example.com.".parse().unwrap();
+        cache.insert_positive(
+            name.clone(),
+            RecordType::A,
+            DNSClass::IN,
+            &[a_record("plain.example.com.", 300)],
+        );
+
+        assert!(cache.get(&name, RecordType::A, DNSClass::IN, false).is_some());
+        assert!(cache.get(&name, RecordType::A, DNSClass::IN, true).is_none());
+    }
+
+    #[test]
+    fn negative_entry_uses_soa_minimum() {
+        use hickory_proto::rr::rdata::SOA;
+
+        let cache = DnsCache::new(&CacheConfig::default());
+        let name: Name = "missing.example.com.".parse().unwrap();
+        let soa = Record::from_rdata(
+            "example.com.".parse().unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                "ns.example.com.".parse().unwrap(),
+                "hostmaster.example.com.".parse().unwrap(),
+                1,
+                3600,
+                1800,
+                604800,
+                120,
+            )),
+        );
+
+        cache.insert_negative(
+            name.clone(),
+            RecordType::A,
+            DNSClass::IN,
+            ResponseCode::NXDomain,
+            &[soa],
+        );
+
+        let hit = cache
+            .get(&name, RecordType::A, DNSClass::IN, false)
+            .expect("cache hit");
+        assert_eq!(hit.response_code, ResponseCode::NXDomain);
+        assert_eq!(hit.soa.unwrap().ttl(), 120);
+
+        // The negative entry above carries no RRSIG, so it must not be
+        // served to a requester asking for DNSSEC data.
+        assert!(cache.get(&name, RecordType::A, DNSClass::IN, true).is_none());
+    }
+
+    #[test]
+    fn aggressive_nsec_synthesizes_for_names_in_gap() {
+        use hickory_proto::rr::{
+            dnssec::rdata::{DNSSECRData, NSEC},
+            rdata::SOA,
+        };
+
+        let cache = DnsCache::new(&CacheConfig {
+            size: 16,
+            min_ttl: 0,
+            max_ttl: 3600,
+            aggressive_nsec: true,
+        });
+
+        let soa = Record::from_rdata(
+            "example.com.".parse().unwrap(),
+            3600,
+            RData::SOA(SOA::new(
+                "ns.example.com.".parse().unwrap(),
+                "hostmaster.example.com.".parse().unwrap(),
+                1,
+                3600,
+                1800,
+                604800,
+                120,
+            )),
+        );
+        let nsec = Record::from_rdata(
+            "a.example.com.".parse().unwrap(),
+            300,
+            RData::DNSSEC(DNSSECRData::NSEC(NSEC::new(
+                "c.example.com.".parse().unwrap(),
+                vec![],
+            ))),
+        );
+
+        cache.insert_negative(
+            "b.example.com.".parse().unwrap(),
+            RecordType::A,
+            DNSClass::IN,
+            ResponseCode::NXDomain,
+            &[soa, nsec],
+        );
+
+        // An exact-match negative cache entry only exists for
+        // "b.example.com.", but "bb.example.com." falls in the same
+        // NSEC gap (a.example.com. < bb.example.com. < c.example.com.)
+        // and should be synthesized without an upstream query.
+        let other: Name = "bb.example.com.".parse().unwrap();
+        let synthesis = cache
+            .synthesize_negative(&other, true)
+            .expect("synthesized from cached NSEC gap");
+        assert_eq!(synthesis.response_code, ResponseCode::NXDomain);
+        assert!(!synthesis.authority.is_empty());
+
+        // Without the DO bit, aggressive synthesis does not apply.
+        assert!(cache.synthesize_negative(&other, false).is_none());
+    }
+}
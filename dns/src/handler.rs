@@ -1,17 +1,20 @@
 use crate::{
+    cache::DnsCache,
+    static_records::StaticAuthority,
     utils::{load_cert_chain, load_default_cert, load_default_key, load_priv_key, new_io_error},
     DNSListenAddr, DnsMessageExchanger,
 };
 use async_trait::async_trait;
 use hickory_proto::{
-    op::{Header, Message, MessageType, OpCode, ResponseCode},
-    rr::RecordType,
+    op::{Edns, Header, Message, MessageType, OpCode, ResponseCode},
+    rr::{Record, RecordType},
 };
 use hickory_server::{
     authority::MessageResponseBuilder,
     server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
     ServerFuture,
 };
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::net::{TcpListener, UdpSocket};
@@ -23,6 +26,8 @@ struct DnsListener<H: RequestHandler> {
 
 struct DnsHandler<X> {
     exchanger: X,
+    cache: Option<Arc<DnsCache>>,
+    static_records: Option<Arc<StaticAuthority>>,
 }
 
 #[derive(Error, Debug)]
@@ -35,6 +40,15 @@ pub enum DNSError {
     QueryFailed(String),
 }
 
+/// The EDNS record to attach to a response built locally (from the cache or
+/// a synthesized denial), echoing the requester's DO bit so a validating
+/// resolver doesn't mistake the signatures we include for an unsolicited,
+/// non-EDNS reply.
+fn locally_answered_edns(request: &Request) -> Option<Edns> {
+    let edns = request.edns()?;
+    edns.dnssec_ok().then(|| edns.clone())
+}
+
 impl<X> DnsHandler<X>
 where
     X: DnsMessageExchanger,
@@ -68,6 +82,84 @@ where
             return Ok(response_handle.send_response(resp).await?);
         }
 
+        if let Some(statics) = &self.static_records {
+            if let Some(rdata) = statics.lookup(request.query().name(), request.query().query_type()) {
+                header.set_authoritative(true);
+                header.set_response_code(ResponseCode::NoError);
+
+                let answers: Vec<Record> = rdata
+                    .iter()
+                    .map(|d| Record::from_rdata(request.query().name().clone(), statics.ttl(), d.clone()))
+                    .collect();
+                header.set_answer_count(answers.len() as u16);
+
+                let resp = builder.build(header, &answers, &[], &[], &[]);
+
+                debug!(
+                    "answering dns query {} from static records with answer {:?}",
+                    request.query().name(),
+                    answers,
+                );
+
+                return Ok(response_handle.send_response(resp).await?);
+            }
+        }
+
+        let dnssec_ok = request.edns().map(|e| e.dnssec_ok()).unwrap_or(false);
+
+        if let Some(cache) = &self.cache {
+            if let Some(hit) = cache.get(
+                request.query().name(),
+                request.query().query_type(),
+                request.query().query_class(),
+                dnssec_ok,
+            ) {
+                header.set_recursion_available(true);
+                header.set_response_code(hit.response_code);
+                header.set_authoritative(false);
+
+                let mut answers = hit.records;
+                answers.extend(hit.rrsigs);
+                let name_servers: Vec<Record> = hit.soa.into_iter().collect();
+
+                header.set_answer_count(answers.len() as u16);
+                header.set_name_server_count(name_servers.len() as u16);
+
+                let mut resp = builder.build(header, &answers, &name_servers, &[], &[]);
+                if let Some(edns) = locally_answered_edns(request) {
+                    resp.set_edns(edns);
+                }
+
+                debug!(
+                    "answering dns query {} from cache with answer {:?}",
+                    request.query().name(),
+                    answers,
+                );
+
+                return Ok(response_handle.send_response(resp).await?);
+            }
+
+            if let Some(synthesis) = cache.synthesize_negative(request.query().name(), dnssec_ok) {
+                header.set_recursion_available(true);
+                header.set_response_code(synthesis.response_code);
+                header.set_authoritative(false);
+                header.set_answer_count(0);
+                header.set_name_server_count(synthesis.authority.len() as u16);
+
+                let mut resp = builder.build(header, &[], &synthesis.authority, &[], &[]);
+                if let Some(edns) = locally_answered_edns(request) {
+                    resp.set_edns(edns);
+                }
+
+                debug!(
+                    "synthesizing negative answer for {} from cached NSEC(3) range",
+                    request.query().name(),
+                );
+
+                return Ok(response_handle.send_response(resp).await?);
+            }
+        }
+
         let mut m = Message::new();
         m.set_op_code(request.op_code());
         m.set_message_type(request.message_type());
@@ -103,6 +195,29 @@ where
                     }
                 }
 
+                if let Some(cache) = &self.cache {
+                    match m.response_code() {
+                        ResponseCode::NoError if !m.answers().is_empty() => {
+                            cache.insert_positive(
+                                request.query().name().clone(),
+                                request.query().query_type(),
+                                request.query().query_class(),
+                                m.answers(),
+                            );
+                        }
+                        ResponseCode::NoError | ResponseCode::NXDomain => {
+                            cache.insert_negative(
+                                request.query().name().clone(),
+                                request.query().query_type(),
+                                request.query().query_class(),
+                                m.response_code(),
+                                m.name_servers(),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+
                 debug!(
                     "answering dns query {} with answer {:?}",
                     request.query().name(),
@@ -151,15 +266,43 @@ where
 
 static DEFAULT_DNS_SERVER_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// A handle to a running DNS listener returned by [`get_dns_listener`].
+///
+/// Dropping the handle without calling [`DnsServerHandle::shutdown`] leaves
+/// the server running; call `shutdown` to have it stop accepting queries and
+/// release its sockets while leaving the rest of the process alone.
+pub struct DnsServerHandle {
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl DnsServerHandle {
+    pub fn shutdown(mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
 pub async fn get_dns_listener<X>(
     listen: DNSListenAddr,
     exchanger: X,
     cwd: &std::path::Path,
-) -> Option<futures::future::BoxFuture<'static, Result<(), DNSError>>>
+) -> Option<(
+    DnsServerHandle,
+    futures::future::BoxFuture<'static, Result<(), DNSError>>,
+)>
 where
     X: DnsMessageExchanger + Sync + Send + Unpin + 'static,
 {
-    let handler = DnsHandler { exchanger };
+    let cache = listen.cache.as_ref().map(|c| Arc::new(DnsCache::new(c)));
+    let static_records = listen
+        .static_records
+        .map(|s| Arc::new(StaticAuthority::new(s.records, s.ttl)));
+    let handler = DnsHandler {
+        exchanger,
+        cache,
+        static_records,
+    };
     let mut s = ServerFuture::new(handler);
 
     let mut has_server = false;
@@ -271,18 +414,71 @@ where
             .ok()?;
     }
 
+    if let Some(c) = listen.doq {
+        has_server = true;
+        UdpSocket::bind(c.addr)
+            .await
+            .and_then(|x| {
+                info!("DoQ dns server listening on: {}", c.addr);
+                if let (Some(k), Some(c)) = (&c.ca_key, &c.ca_cert) {
+                    debug!("using custom key and cert for doq: {}/{}", k, c);
+                }
+
+                let server_key = c
+                    .ca_key
+                    .map(|x| load_priv_key(&cwd.join(x)))
+                    .transpose()?
+                    .unwrap_or(load_default_key());
+                let server_cert = c
+                    .ca_cert
+                    .map(|x| load_cert_chain(&cwd.join(x)))
+                    .transpose()?
+                    .unwrap_or(load_default_cert());
+                s.register_quic_listener(
+                    x,
+                    DEFAULT_DNS_SERVER_TIMEOUT,
+                    (server_cert, server_key),
+                    c.hostname,
+                )?;
+                Ok(())
+            })
+            .ok()?;
+    }
+
     if !has_server {
         return None;
     }
 
     let mut l = DnsListener { server: s };
-
-    Some(Box::pin(async move {
-        l.server.block_until_done().await.map_err(|x| {
-            warn!("dns server error: {}", x);
-            DNSError::Io(new_io_error(format!("dns server error: {}", x)))
-        })
-    }))
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+
+    let fut = Box::pin(async move {
+        tokio::select! {
+            res = l.server.block_until_done() => {
+                res.map_err(|x| {
+                    warn!("dns server error: {}", x);
+                    DNSError::Io(new_io_error(format!("dns server error: {}", x)))
+                })
+            }
+            _ = async {
+                if shutdown_rx.await.is_err() {
+                    // The handle was dropped without `shutdown`: keep serving.
+                    std::future::pending::<()>().await;
+                }
+            } => {
+                info!("dns server shutting down");
+                drop(l);
+                Ok(())
+            }
+        }
+    });
+
+    Some((
+        DnsServerHandle {
+            shutdown: Some(shutdown_tx),
+        },
+        fut,
+    ))
 }
 
 #[cfg(test)]
@@ -297,6 +493,7 @@ mod tests {
     use hickory_proto::{
         h2::HttpsClientStreamBuilder,
         h3::H3ClientStreamBuilder,
+        quic::QuicClientStreamBuilder,
         rr::{rdata::A, DNSClass, Name, RData, RecordType},
         rustls::tls_client_connect,
         tcp::TcpClientStream,
@@ -307,7 +504,8 @@ mod tests {
 
     use crate::{
         tls::{self, global_root_store},
-        DNSListenAddr, DoH3Config, DoHConfig, DoTConfig, MockDnsMessageExchanger,
+        DNSListenAddr, DoH3Config, DoHConfig, DoQConfig, DoTConfig, MockDnsMessageExchanger,
+        StaticRecords,
     };
 
     async fn send_query(client: &mut AsyncClient) {
@@ -380,14 +578,23 @@ mod tests {
                 ca_key: None,
                 ca_cert: None,
             }),
+            doq: Some(DoQConfig {
+                addr: "127.0.0.1:53557".parse().unwrap(),
+                hostname: Some("dns.example.com".to_string()),
+                ca_key: None,
+                ca_cert: None,
+            }),
+            cache: None,
+            static_records: None,
         };
 
         let listener =
             super::get_dns_listener(cfg, mock_exchanger, std::path::Path::new(".")).await;
 
         assert!(listener.is_some());
+        let (_handle, server) = listener.unwrap();
         tokio::spawn(async move {
-            listener.unwrap().await.unwrap();
+            server.await.unwrap();
         });
 
         let stream = UdpClientStream::<TokioUdpSocket>::new("127.0.0.1:53553".parse().unwrap());
@@ -470,5 +677,99 @@ mod tests {
         tokio::spawn(handle);
 
         send_query(&mut client).await;
+
+        let mut tls_config = ClientConfig::builder()
+            .with_root_certificates(global_root_store().clone())
+            .with_no_client_auth();
+        tls_config.alpn_protocols = vec!["doq".into()];
+
+        tls_config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(tls::DummyTlsVerifier::new()));
+
+        let stream = QuicClientStreamBuilder::default()
+            .crypto_config(tls_config)
+            .build(
+                "127.0.0.1:53557".parse().unwrap(),
+                "dns.example.com".to_owned(),
+            );
+
+        let (mut client, handle) = client::AsyncClient::connect(stream).await.unwrap();
+        tokio::spawn(handle);
+
+        send_query(&mut client).await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_handle_stops_listener() {
+        let mut mock_exchanger = MockDnsMessageExchanger::new();
+        mock_exchanger.expect_ipv6().returning(|| false);
+
+        let cfg = DNSListenAddr {
+            udp: Some("127.0.0.1:53558".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let (handle, server) = super::get_dns_listener(cfg, mock_exchanger, std::path::Path::new("."))
+            .await
+            .unwrap();
+
+        let join = tokio::spawn(server);
+        handle.shutdown();
+
+        tokio::time::timeout(Duration::from_secs(5), join)
+            .await
+            .expect("server future should resolve promptly after shutdown")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_static_record_override_skips_exchanger() {
+        use std::collections::HashMap;
+
+        let mut mock_exchanger = MockDnsMessageExchanger::new();
+        mock_exchanger.expect_ipv6().returning(|| false);
+        mock_exchanger
+            .expect_exchange()
+            .returning(|_| panic!("statically overridden queries must not reach the exchanger"));
+
+        let mut records = HashMap::new();
+        records.insert(
+            ("router.internal.".parse().unwrap(), RecordType::A),
+            vec![RData::A(A::new(10, 0, 0, 1))],
+        );
+
+        let cfg = DNSListenAddr {
+            udp: Some("127.0.0.1:53559".parse().unwrap()),
+            static_records: Some(StaticRecords { records, ttl: 120 }),
+            ..Default::default()
+        };
+
+        let (_handle, server) =
+            super::get_dns_listener(cfg, mock_exchanger, std::path::Path::new("."))
+                .await
+                .unwrap();
+        tokio::spawn(server);
+
+        let stream = UdpClientStream::<TokioUdpSocket>::new("127.0.0.1:53559".parse().unwrap());
+        let (mut client, handle) = client::AsyncClient::connect(stream).await.unwrap();
+        tokio::spawn(handle);
+
+        let response = client
+            .query(
+                Name::from_ascii("router.internal.").unwrap(),
+                DNSClass::IN,
+                RecordType::A,
+            )
+            .await
+            .unwrap();
+
+        let answers = response.answers();
+        if let RData::A(ref ip) = answers[0].data() {
+            assert_eq!(*ip, A::new(10, 0, 0, 1))
+        } else {
+            unreachable!("unexpected result")
+        }
     }
 }
\ No newline at end of file
@@ -1,15 +1,22 @@
+use std::collections::HashMap;
 use std::future::Future;
 use std::net::SocketAddr;
 
 use hickory_proto::op::Message;
+use hickory_proto::rr::{Name, RData, RecordType};
 use serde::Deserialize;
 
+mod cache;
 mod dummy_keys;
 mod handler;
+mod nsec;
+mod static_records;
 #[cfg(test)]
 mod tls;
+mod ttl;
 mod utils;
 
+pub use cache::CacheConfig;
 pub use handler::get_dns_listener;
 pub use handler::DNSError;
 
@@ -39,9 +46,27 @@ pub struct DoTConfig {
     pub ca_key: DnsServerKey,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct DoQConfig {
+    pub addr: SocketAddr,
+    pub ca_cert: DnsServerCert,
+    pub ca_key: DnsServerKey,
+    pub hostname: Option<String>,
+}
+
 pub type DnsServerKey = Option<String>;
 pub type DnsServerCert = Option<String>;
 
+/// Local name -> record overrides, answered before the upstream exchanger is
+/// ever consulted. A name whose first label is `*` (e.g. `*.internal`) acts
+/// as a wildcard-suffix match for itself and any name underneath it.
+#[derive(Debug, Default, Clone)]
+pub struct StaticRecords {
+    pub records: HashMap<(Name, RecordType), Vec<RData>>,
+    pub ttl: u32,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct DNSListenAddr {
     pub udp: Option<SocketAddr>,
@@ -49,6 +74,9 @@ pub struct DNSListenAddr {
     pub doh: Option<DoHConfig>,
     pub dot: Option<DoTConfig>,
     pub doh3: Option<DoH3Config>,
+    pub doq: Option<DoQConfig>,
+    pub cache: Option<CacheConfig>,
+    pub static_records: Option<StaticRecords>,
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -0,0 +1,286 @@
+use std::{num::NonZeroUsize, sync::Mutex, time::Instant};
+
+use data_encoding::BASE32HEX_NOPAD;
+use hickory_proto::{
+    rr::{dnssec::rdata::DNSSECRData, Name, RData, Record, RecordType},
+    serialize::binary::{BinEncodable, BinEncoder},
+};
+use lru::LruCache;
+use sha1::{Digest, Sha1};
+
+use crate::ttl::aged_ttl;
+
+/// Denial-of-existence gap cached from a signed upstream NXDOMAIN/NODATA
+/// response, used to synthesize answers for later queries that provably
+/// fall inside it without another upstream round-trip (RFC 8198).
+#[derive(Debug, Clone)]
+enum Range {
+    Nsec {
+        owner: Name,
+        next: Name,
+    },
+    Nsec3 {
+        hashed_owner: String,
+        next_hashed_owner: String,
+        salt: Vec<u8>,
+        iterations: u16,
+        opt_out: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    range: Range,
+    records: Vec<Record>,
+    inserted_at: Instant,
+    ttl: u32,
+}
+
+impl Entry {
+    fn aged_ttl(&self) -> Option<u32> {
+        aged_ttl(self.inserted_at, self.ttl)
+    }
+}
+
+/// A synthesized denial of existence, ready to be served as an NXDOMAIN
+/// answer: the covering NSEC(3) and RRSIG records that prove it, TTL-aged
+/// like any other cache hit.
+pub struct Synthesized {
+    pub authority: Vec<Record>,
+}
+
+/// Cache of NSEC/NSEC3 denial-of-existence ranges observed in signed
+/// upstream responses, bounded to the same size as the response cache it
+/// rides alongside.
+///
+/// Entries are bucketed by the zone they were observed under, so a lookup
+/// for `name` only ever touches the (few) zones that are actually its
+/// ancestors instead of scanning — and NSEC3-hashing against — every
+/// cached range regardless of zone. The buckets themselves are kept in an
+/// LRU map keyed by zone and bounded to `capacity` zones, so a flood of
+/// denials across many distinct zones evicts the least-recently-used zone
+/// rather than growing without bound.
+pub struct AggressiveNegativeCache {
+    entries: Mutex<LruCache<Name, Vec<Entry>>>,
+    capacity: usize,
+}
+
+impl AggressiveNegativeCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            entries: Mutex::new(LruCache::new(NonZeroUsize::new(capacity).unwrap())),
+            capacity,
+        }
+    }
+
+    /// Records the NSEC/NSEC3 (+ covering RRSIG) records found in the
+    /// authority section of a signed denial response.
+    pub fn observe(&self, zone: &Name, authority: &[Record]) {
+        for record in authority {
+            let range = match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::NSEC(nsec))) => Range::Nsec {
+                    owner: record.name().clone(),
+                    next: nsec.next_domain_name().clone(),
+                },
+                Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) => Range::Nsec3 {
+                    hashed_owner: nsec3_owner_label(record.name()),
+                    next_hashed_owner: BASE32HEX_NOPAD
+                        .encode(nsec3.next_hashed_owner_name())
+                        .to_ascii_lowercase(),
+                    salt: nsec3.salt().to_vec(),
+                    iterations: nsec3.iterations(),
+                    opt_out: nsec3.opt_out(),
+                },
+                _ => continue,
+            };
+
+            let rrsigs = authority
+                .iter()
+                .filter(|r| {
+                    r.record_type() == RecordType::RRSIG && r.name() == record.name()
+                })
+                .cloned();
+
+            let Some(ttl) = std::iter::once(record.clone())
+                .chain(rrsigs.clone())
+                .map(|r| r.ttl())
+                .min()
+            else {
+                continue;
+            };
+
+            let mut records = vec![record.clone()];
+            records.extend(rrsigs);
+
+            self.insert(
+                zone,
+                Entry {
+                    range,
+                    records,
+                    inserted_at: Instant::now(),
+                    ttl,
+                },
+            );
+        }
+    }
+
+    fn insert(&self, zone: &Name, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        let bucket = entries.get_or_insert_mut(zone.clone(), Vec::new);
+        bucket.retain(Entry::aged_ttl_is_some);
+        if bucket.len() >= self.capacity {
+            bucket.remove(0);
+        }
+        bucket.push(entry);
+    }
+
+    /// Synthesizes a denial of existence for `name` if it provably falls
+    /// inside a cached NSEC/NSEC3 gap under one of its ancestor zones.
+    ///
+    /// Only the zones that are actual ancestors of `name` are consulted —
+    /// walking up from `name` to the root one label at a time and looking
+    /// each candidate zone up directly, rather than scanning every cached
+    /// range regardless of zone.
+    pub fn synthesize(&self, name: &Name) -> Option<Synthesized> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let mut zone = name.clone();
+        loop {
+            if let Some(bucket) = entries.get_mut(&zone) {
+                bucket.retain(Entry::aged_ttl_is_some);
+                if let Some(hit) = bucket.iter().find(|e| covers(&e.range, name)) {
+                    let ttl = hit.aged_ttl()?;
+                    let authority = hit
+                        .records
+                        .iter()
+                        .cloned()
+                        .map(|mut r| {
+                            r.set_ttl(ttl);
+                            r
+                        })
+                        .collect();
+                    return Some(Synthesized { authority });
+                }
+            }
+
+            if zone.is_root() {
+                return None;
+            }
+            zone = zone.base_name();
+        }
+    }
+}
+
+impl Entry {
+    fn aged_ttl_is_some(entry: &Entry) -> bool {
+        entry.aged_ttl().is_some()
+    }
+}
+
+fn covers(range: &Range, name: &Name) -> bool {
+    match range {
+        Range::Nsec { owner, next } => canonically_between(owner, name, next),
+        Range::Nsec3 {
+            hashed_owner,
+            next_hashed_owner,
+            salt,
+            iterations,
+            opt_out,
+        } => {
+            // An opt-out range only denies signed delegations, not plain
+            // names, so it cannot be used to synthesize a firm answer.
+            if *opt_out {
+                return false;
+            }
+            let hashed_name = nsec3_hash(name, *iterations, salt);
+            string_between(hashed_owner, &hashed_name, next_hashed_owner)
+        }
+    }
+}
+
+/// Canonical-order "strictly between" check with wraparound at the last
+/// NSEC record in a zone, whose `next` points back to the zone apex.
+fn canonically_between(owner: &Name, name: &Name, next: &Name) -> bool {
+    let owner = owner.to_lowercase();
+    let name = name.to_lowercase();
+    let next = next.to_lowercase();
+
+    if owner < next {
+        owner < name && name < next
+    } else {
+        name > owner || name < next
+    }
+}
+
+fn string_between(owner: &str, name: &str, next: &str) -> bool {
+    if owner < next {
+        owner.as_bytes() < name.as_bytes() && name.as_bytes() < next.as_bytes()
+    } else {
+        name.as_bytes() > owner.as_bytes() || name.as_bytes() < next.as_bytes()
+    }
+}
+
+fn nsec3_owner_label(owner: &Name) -> String {
+    owner
+        .iter()
+        .next()
+        .map(|label| String::from_utf8_lossy(label).to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn nsec3_hash(name: &Name, iterations: u16, salt: &[u8]) -> String {
+    let mut wire = Vec::new();
+    {
+        let mut encoder = BinEncoder::new(&mut wire);
+        name.to_lowercase()
+            .emit(&mut encoder)
+            .expect("name always encodes");
+    }
+
+    let mut digest = Sha1::digest([wire.as_slice(), salt].concat()).to_vec();
+    for _ in 0..iterations {
+        digest = Sha1::digest([digest.as_slice(), salt].concat()).to_vec();
+    }
+
+    BASE32HEX_NOPAD.encode(&digest).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nsec_range_covers_name_in_gap() {
+        let owner: Name = "a.example.com.".parse().unwrap();
+        let next: Name = "c.example.com.".parse().unwrap();
+        let inside: Name = "b.example.com.".parse().unwrap();
+        let outside: Name = "d.example.com.".parse().unwrap();
+
+        assert!(canonically_between(&owner, &inside, &next));
+        assert!(!canonically_between(&owner, &outside, &next));
+    }
+
+    #[test]
+    fn nsec_range_wraps_around_zone_apex() {
+        // The last NSEC in a zone points back to the apex.
+        let owner: Name = "z.example.com.".parse().unwrap();
+        let next: Name = "example.com.".parse().unwrap();
+        let inside: Name = "zz.example.com.".parse().unwrap();
+
+        assert!(canonically_between(&owner, &inside, &next));
+    }
+
+    #[test]
+    fn nsec3_opt_out_range_never_synthesizes() {
+        let range = Range::Nsec3 {
+            hashed_owner: "aaaa".into(),
+            next_hashed_owner: "zzzz".into(),
+            salt: vec![],
+            iterations: 0,
+            opt_out: true,
+        };
+        let name: Name = "anything.example.com.".parse().unwrap();
+        assert!(!covers(&range, &name));
+    }
+}
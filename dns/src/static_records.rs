@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use hickory_proto::rr::{Name, RData, RecordType};
+
+/// Local name -> record overrides answered directly by [`crate::handler`]
+/// instead of forwarding to the upstream [`crate::DnsMessageExchanger`].
+///
+/// This is the server-side analogue of a client-side DNS address override
+/// table: useful for split-horizon setups, captive-portal hostnames, and
+/// pinning internal names. A name whose first label is `*` (e.g.
+/// `*.internal`) matches itself and any name underneath it.
+pub struct StaticAuthority {
+    exact: HashMap<(Name, RecordType), Vec<RData>>,
+    wildcards: Vec<(Name, RecordType, Vec<RData>)>,
+    ttl: u32,
+}
+
+impl StaticAuthority {
+    pub fn new(records: HashMap<(Name, RecordType), Vec<RData>>, ttl: u32) -> Self {
+        let mut exact = HashMap::new();
+        let mut wildcards = Vec::new();
+
+        for ((name, record_type), rdata) in records {
+            if name.is_wildcard() {
+                wildcards.push((name.base_name(), record_type, rdata));
+            } else {
+                exact.insert((name, record_type), rdata);
+            }
+        }
+
+        Self {
+            exact,
+            wildcards,
+            ttl,
+        }
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// Returns the overridden records for `name`/`record_type`, preferring an
+    /// exact match over a wildcard-suffix one.
+    pub fn lookup(&self, name: &Name, record_type: RecordType) -> Option<&[RData]> {
+        if let Some(rdata) = self.exact.get(&(name.clone(), record_type)) {
+            return Some(rdata);
+        }
+
+        self.wildcards
+            .iter()
+            .find(|(suffix, rtype, _)| *rtype == record_type && suffix.zone_of(name))
+            .map(|(_, _, rdata)| rdata.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_proto::rr::rdata::A;
+
+    use super::*;
+
+    fn a(ip: [u8; 4]) -> RData {
+        RData::A(A::new(ip[0], ip[1], ip[2], ip[3]))
+    }
+
+    #[test]
+    fn exact_match() {
+        let mut records = HashMap::new();
+        records.insert(
+            ("router.lan.".parse().unwrap(), RecordType::A),
+            vec![a([192, 168, 1, 1])],
+        );
+        let authority = StaticAuthority::new(records, 300);
+
+        let name: Name = "router.lan.".parse().unwrap();
+        assert!(authority.lookup(&name, RecordType::A).is_some());
+        assert!(authority.lookup(&name, RecordType::AAAA).is_none());
+    }
+
+    #[test]
+    fn wildcard_suffix_match() {
+        let mut records = HashMap::new();
+        records.insert(
+            ("*.internal.".parse().unwrap(), RecordType::A),
+            vec![a([10, 0, 0, 1])],
+        );
+        let authority = StaticAuthority::new(records, 60);
+
+        let direct: Name = "internal.".parse().unwrap();
+        let nested: Name = "svc.db.internal.".parse().unwrap();
+        let other: Name = "example.com.".parse().unwrap();
+
+        assert!(authority.lookup(&direct, RecordType::A).is_some());
+        assert!(authority.lookup(&nested, RecordType::A).is_some());
+        assert!(authority.lookup(&other, RecordType::A).is_none());
+    }
+}
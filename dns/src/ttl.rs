@@ -0,0 +1,9 @@
+use std::time::Instant;
+
+/// Ages `ttl` down by the time elapsed since `inserted_at`, returning `None`
+/// once it has been exhausted. Shared by [`crate::cache`] and [`crate::nsec`],
+/// whose cached entries both expire on the same wall-clock rule.
+pub(crate) fn aged_ttl(inserted_at: Instant, ttl: u32) -> Option<u32> {
+    let elapsed = Instant::now().saturating_duration_since(inserted_at).as_secs() as u32;
+    ttl.checked_sub(elapsed).filter(|ttl| *ttl > 0)
+}